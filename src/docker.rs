@@ -1,5 +1,7 @@
+use crate::convert_to_bytes::convert_to_bytes;
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::process::Command;
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -9,9 +11,94 @@ pub struct DockerContainerStats {
 	pub mem_usage: String,
 	pub mem_limit: String,
 	pub net_io: String,
+	pub block_io: String,
 }
 
-const DOCKER_FORMAT: &str = r#"{"container": "{{.Name}}", "cpu_perc": "{{.CPUPerc}}", "mem_usage": "{{.MemUsage}}", "mem_limit": "{{.MemLimit}}", "net_io": "{{.NetIO}}"}"#;
+/// Numeric, backend-agnostic view of a container's stats. Both the `docker
+/// stats` CLI backend and the Engine API backend produce this shape so the
+/// rest of the exporter never has to care which one ran.
+#[derive(Debug, Clone)]
+pub struct ContainerMetrics {
+	pub container: String,
+	/// Short (12-character) container ID.
+	pub id: String,
+	/// Image name as specified when the container was created, e.g. `nginx:latest`.
+	pub image: String,
+	/// The container's own Docker labels, e.g. `com.docker.compose.project`.
+	pub labels: HashMap<String, String>,
+	pub cpu_percent: f64,
+	pub mem_usage_bytes: f64,
+	pub mem_limit_bytes: f64,
+	pub net_input_bytes: f64,
+	pub net_output_bytes: f64,
+	pub block_read_bytes: f64,
+	pub block_write_bytes: f64,
+}
+
+/// Docker labels/image/ID metadata for a single container, fetched
+/// separately from `docker stats` since that command doesn't expose them.
+#[derive(Debug, Clone, Default)]
+struct ContainerMetadata {
+	id: String,
+	image: String,
+	labels: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct InspectConfig {
+	#[serde(rename = "Image")]
+	image: String,
+	#[serde(rename = "Labels")]
+	labels: Option<HashMap<String, String>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct InspectResult {
+	#[serde(rename = "Id")]
+	id: String,
+	#[serde(rename = "Name")]
+	name: String,
+	#[serde(rename = "Config")]
+	config: InspectConfig,
+}
+
+const SHORT_ID_LEN: usize = 12;
+
+/// Runs `docker inspect` for the given container names and returns their
+/// labels/image/ID metadata, keyed by container name.
+fn inspect_metadata(names: &[String]) -> Result<HashMap<String, ContainerMetadata>> {
+	if names.is_empty() {
+		return Ok(HashMap::new());
+	}
+
+	let output = Command::new("docker").arg("inspect").args(names).output()?;
+
+	if !output.status.success() {
+		let stderr = String::from_utf8_lossy(&output.stderr);
+		eprintln!("`docker inspect` returned non-zero exit code with output: \n{}", stderr);
+		return Err(anyhow!("Docker inspect command did bad :("));
+	}
+
+	let results = serde_json::from_slice::<Vec<InspectResult>>(&output.stdout)?;
+
+	Ok(results
+		.into_iter()
+		.map(|result| {
+			let name = result.name.trim_start_matches('/').to_string();
+			let short_id = result.id.chars().take(SHORT_ID_LEN).collect();
+			(
+				name,
+				ContainerMetadata {
+					id: short_id,
+					image: result.config.image,
+					labels: result.config.labels.unwrap_or_default(),
+				},
+			)
+		})
+		.collect())
+}
+
+const DOCKER_FORMAT: &str = r#"{"container": "{{.Name}}", "cpu_perc": "{{.CPUPerc}}", "mem_usage": "{{.MemUsage}}", "mem_limit": "{{.MemLimit}}", "net_io": "{{.NetIO}}", "block_io": "{{.BlockIO}}"}"#;
 
 pub fn stats() -> Result<Vec<DockerContainerStats>> {
 	let output = Command::new("docker")
@@ -35,3 +122,79 @@ pub fn stats() -> Result<Vec<DockerContainerStats>> {
 	let result = serde_json::from_str::<Vec<DockerContainerStats>>(json_string.as_str())?;
 	Ok(result)
 }
+
+/// Runs `docker stats` and converts the resulting human-formatted strings
+/// into [`ContainerMetrics`], enriched with each container's labels, image,
+/// and short ID from `docker inspect`.
+pub fn collect_metrics() -> Result<Vec<ContainerMetrics>> {
+	let raw_stats = stats()?;
+	let names: Vec<String> = raw_stats.iter().map(|s| s.container.clone()).collect();
+	let mut metadata = inspect_metadata(&names)?;
+
+	raw_stats
+		.into_iter()
+		.map(|stat| {
+			let meta = metadata.remove(&stat.container).unwrap_or_default();
+			stat.into_metrics(meta)
+		})
+		.collect()
+}
+
+fn parse_io_str(str: String) -> Result<f64> {
+	let backwards_unit = str
+		.chars()
+		.rev()
+		.take_while(|c| c.is_alphabetic())
+		.collect::<String>();
+	let unit = backwards_unit.chars().rev().collect::<String>();
+	let index = str.len() - unit.len();
+	let value = &str[0..index];
+	let float_value = value.parse::<f64>()?;
+	let result = convert_to_bytes(float_value, unit)?;
+	Ok(result)
+}
+
+fn parse_percent_str(percent_string: &str) -> Result<f64> {
+	let trimmed = percent_string
+		.strip_suffix('%')
+		.ok_or_else(|| anyhow!("Bad percentage string: '{}'", percent_string))?;
+	Ok(trimmed.parse()?)
+}
+
+fn parse_pair_str(pair_string: &str, what: &str) -> Result<(f64, f64)> {
+	let mut parts: Vec<&str> = pair_string.split(" / ").collect();
+	let (Some(second), Some(first)) = (parts.pop(), parts.pop()) else {
+		return Err(anyhow!("Bad {} string: '{}'", what, pair_string));
+	};
+
+	let first = parse_io_str(first.to_string())?;
+	let second = parse_io_str(second.to_string())?;
+
+	Ok((first, second))
+}
+
+impl DockerContainerStats {
+	/// Parses the human-formatted strings `docker stats` prints back into
+	/// numeric bytes/percent, losing no information `docker stats` didn't
+	/// already throw away by formatting them in the first place.
+	fn into_metrics(self, metadata: ContainerMetadata) -> Result<ContainerMetrics> {
+		let cpu_percent = parse_percent_str(&self.cpu_perc)?;
+		let (mem_usage_bytes, mem_limit_bytes) = parse_pair_str(&self.mem_usage, "memory usage")?;
+		let (net_input_bytes, net_output_bytes) = parse_pair_str(&self.net_io, "netio")?;
+		let (block_read_bytes, block_write_bytes) = parse_pair_str(&self.block_io, "block IO")?;
+
+		Ok(ContainerMetrics {
+			container: self.container,
+			id: metadata.id,
+			image: metadata.image,
+			labels: metadata.labels,
+			cpu_percent,
+			mem_usage_bytes,
+			mem_limit_bytes,
+			net_input_bytes,
+			net_output_bytes,
+			block_read_bytes,
+			block_write_bytes,
+		})
+	}
+}
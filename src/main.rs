@@ -1,19 +1,42 @@
 mod convert_to_bytes;
 mod docker;
+mod docker_api;
 mod error;
+mod openmetrics;
+mod stats_cache;
 
-use crate::convert_to_bytes::convert_to_bytes;
-use crate::docker::DockerContainerStats;
+use crate::docker::ContainerMetrics;
 use crate::error::ApiResult;
+use crate::stats_cache::StatsCache;
 use anyhow::{anyhow, Result};
+use axum::http::HeaderMap;
 use axum::{routing::get, Router};
-use clap::Parser;
-use prometheus::core::{AtomicF64, GenericGauge};
-use prometheus::{Encoder, Gauge, Opts, Registry, TextEncoder};
+use clap::{Parser, ValueEnum};
+use prometheus::core::{AtomicF64, GenericCounter, GenericGauge};
+use prometheus::{Counter, Encoder, Gauge, Opts, Registry, TextEncoder};
 use std::collections::HashMap;
+use std::time::Duration;
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+/// Which backend to collect container stats from.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+	/// Shell out to `docker stats --no-stream` and parse its output.
+	Cli,
+	/// Talk to the Docker Engine API directly over its Unix socket.
+	DockerApi,
+}
+
+/// Which text exposition format to render metrics in.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+	/// Classic Prometheus text format.
+	Prometheus,
+	/// OpenMetrics exposition format, with declared units and a trailing `# EOF`.
+	Openmetrics,
+}
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
@@ -32,6 +55,42 @@ struct Args {
 	/// Number of worker threads for the runtime
 	#[arg(short, long, default_value = "4")]
 	threads: usize,
+
+	/// Where to collect container stats from
+	#[arg(long, value_enum, default_value_t = Source::Cli)]
+	source: Source,
+
+	/// How often to refresh the background stats cache, in seconds (must be
+	/// non-zero: a zero interval would panic the background collector)
+	#[arg(long, default_value = "10", value_parser = clap::value_parser!(u64).range(1..))]
+	scrape_interval: u64,
+
+	/// Promote a container's own Docker label into a Prometheus label, in
+	/// format "prom_label1=docker.label/one,prom_label2=docker.label/two"
+	#[arg(long)]
+	label_from: Option<String>,
+
+	/// Path the metrics are served on
+	#[arg(long, default_value = "/metrics")]
+	metrics_path: String,
+
+	/// Optional second address to serve metrics (and /health) on, in
+	/// addition to --bind-address. Useful for exposing metrics on a
+	/// dedicated port separate from other traffic.
+	#[arg(long)]
+	metrics_address: Option<String>,
+
+	/// Default text exposition format. Overridden by an `Accept` header that
+	/// asks for OpenMetrics.
+	#[arg(long, value_enum, default_value_t = Format::Prometheus)]
+	format: Format,
+}
+
+async fn collect_metrics(source: Source) -> Result<Vec<ContainerMetrics>> {
+	match source {
+		Source::Cli => docker::collect_metrics(),
+		Source::DockerApi => docker_api::collect_metrics().await,
+	}
 }
 
 fn parse_labels(labels_str: Option<String>) -> Result<HashMap<String, String>> {
@@ -61,32 +120,15 @@ fn parse_labels(labels_str: Option<String>) -> Result<HashMap<String, String>> {
 	Ok(labels)
 }
 
-fn percent_gauge(
-	name: String,
-	mut percent_string: String,
-	help: String,
-	container_name: &str,
-	labels: &HashMap<String, String>,
-) -> Result<GenericGauge<AtomicF64>> {
-	percent_string.pop();
-	let value: f64 = percent_string.parse()?;
-	get_gauge(name, help, value, container_name, labels)
-}
-
 fn get_gauge(
 	name: String,
 	help: String,
 	value: f64,
-	container_name: &str,
-	labels: &HashMap<String, String>,
+	dimensions: &HashMap<String, String>,
 ) -> Result<GenericGauge<AtomicF64>> {
 	let mut opts = Opts::new(name.replace("-", "_"), help);
 
-	// Add container name as a label
-	opts = opts.const_label("container", container_name);
-
-	// Add user-defined labels
-	for (key, val) in labels {
+	for (key, val) in dimensions {
 		opts = opts.const_label(key, val);
 	}
 
@@ -95,154 +137,231 @@ fn get_gauge(
 	Ok(gauge)
 }
 
-fn parse_io_str(str: String) -> Result<f64> {
-	let backwards_unit = str
-		.chars()
-		.rev()
-		.take_while(|c| c.is_alphabetic())
-		.collect::<String>();
-	let unit = backwards_unit.chars().rev().collect::<String>();
-	let index = str.len() - unit.len();
-	let value = &str[0..index];
-	let float_value = value.parse::<f64>()?;
-	let result = convert_to_bytes(float_value, unit)?;
-	Ok(result)
-}
-
-fn parse_netio_str(netio_string: &str) -> Result<(f64, f64)> {
-	let mut input_output: Vec<&str> = netio_string.split(" / ").collect();
-	let (Some(output), Some(input)) = (input_output.pop(), input_output.pop()) else {
-		return Err(anyhow!("Bad netio string: '{}'", netio_string));
-	};
-
-	let inp = parse_io_str(input.to_string())?;
-	let out = parse_io_str(output.to_string())?;
-
-	Ok((inp, out))
-}
-
-fn parse_blockio_str(blockio_string: &str) -> Result<(f64, f64)> {
-	let mut input_output: Vec<&str> = blockio_string.split(" / ").collect();
-	let (Some(output), Some(input)) = (input_output.pop(), input_output.pop()) else {
-		return Err(anyhow!("Bad block IO string: '{}'", blockio_string));
-	};
+/// Like [`get_gauge`] but for values that only ever go up, such as
+/// cumulative network/block I/O. Emitted with a `_total` suffix so
+/// `rate()`/`increase()` work and counter resets are detected.
+fn get_counter(
+	name: String,
+	help: String,
+	value: f64,
+	dimensions: &HashMap<String, String>,
+) -> Result<GenericCounter<AtomicF64>> {
+	let mut opts = Opts::new(format!("{}_total", name.replace("-", "_")), help);
 
-	let inp = parse_io_str(input.to_string())?;
-	let out = parse_io_str(output.to_string())?;
+	for (key, val) in dimensions {
+		opts = opts.const_label(key, val);
+	}
 
-	Ok((inp, out))
+	let counter = Counter::with_opts(opts)?;
+	counter.inc_by(value);
+	Ok(counter)
 }
 
-fn parse_mem_usage_str(mem_usage_string: &str) -> Result<(f64, f64)> {
-	let mut usage_limit: Vec<&str> = mem_usage_string.split(" / ").collect();
-	let (Some(limit), Some(usage)) = (usage_limit.pop(), usage_limit.pop()) else {
-		return Err(anyhow!("Bad memory usage string: '{}'", mem_usage_string));
-	};
+/// Builds the set of Prometheus labels for a container's metrics: its name,
+/// image, short ID, any Docker labels promoted via `--label-from`, and the
+/// global `--labels`.
+fn dimensions_for(
+	stat: &ContainerMetrics,
+	labels: &HashMap<String, String>,
+	label_from: &HashMap<String, String>,
+) -> HashMap<String, String> {
+	let mut dimensions = HashMap::new();
+	dimensions.insert("container".to_string(), stat.container.clone());
+	dimensions.insert("image".to_string(), stat.image.clone());
+	dimensions.insert("id".to_string(), stat.id.clone());
+
+	// Every promoted label must be present on every container's dimension
+	// set, even as an empty string — Registry::register keys consistency on
+	// the set of const-label *names*, so a container missing the Docker
+	// label would otherwise produce a different label-name set and make the
+	// second container's registration fail.
+	for (prom_label, docker_label) in label_from {
+		let value = stat.labels.get(docker_label).cloned().unwrap_or_default();
+		dimensions.insert(prom_label.clone(), value);
+	}
 
-	let usage_bytes = parse_io_str(usage.to_string())?;
-	let limit_bytes = parse_io_str(limit.to_string())?;
+	for (key, val) in labels {
+		dimensions.insert(key.clone(), val.clone());
+	}
 
-	Ok((usage_bytes, limit_bytes))
+	dimensions
 }
 
 fn gauges_for_container(
-	stat: &DockerContainerStats,
-	labels: &HashMap<String, String>,
+	stat: &ContainerMetrics,
+	dimensions: &HashMap<String, String>,
 ) -> Result<Vec<GenericGauge<AtomicF64>>> {
-	let cpu_gauge = percent_gauge(
+	let cpu_gauge = get_gauge(
 		"container_cpu_usage".to_string(),
-		stat.cpu_perc.clone(),
 		"CPU usage percentage for container".to_string(),
-		&stat.container,
-		labels,
+		stat.cpu_percent,
+		dimensions,
+	)?;
+	let cpu_ratio_gauge = get_gauge(
+		"container_cpu_usage_ratio".to_string(),
+		"CPU usage as a 0-1 ratio for container".to_string(),
+		stat.cpu_percent / 100.0,
+		dimensions,
 	)?;
-	let (mem_usage_bytes, mem_limit_bytes) = parse_mem_usage_str(stat.mem_usage.as_str())?;
 	let mem_usage_gauge = get_gauge(
 		"container_memory_usage_bytes".to_string(),
 		"Memory usage in bytes for container".to_string(),
-		mem_usage_bytes,
-		&stat.container,
-		labels,
+		stat.mem_usage_bytes,
+		dimensions,
 	)?;
 	let mem_limit_gauge = get_gauge(
 		"container_memory_limit_bytes".to_string(),
 		"Memory limit in bytes for container".to_string(),
-		mem_limit_bytes,
-		&stat.container,
-		labels,
+		stat.mem_limit_bytes,
+		dimensions,
 	)?;
-	let (input, output) = parse_netio_str(stat.net_io.as_str())?;
-	let net_input_gauge = get_gauge(
+
+	Ok(vec![cpu_gauge, cpu_ratio_gauge, mem_usage_gauge, mem_limit_gauge])
+}
+
+fn counters_for_container(
+	stat: &ContainerMetrics,
+	dimensions: &HashMap<String, String>,
+) -> Result<Vec<GenericCounter<AtomicF64>>> {
+	let net_input_counter = get_counter(
 		"container_network_input_bytes".to_string(),
-		"Network input bytes for container".to_string(),
-		input,
-		&stat.container,
-		labels,
+		"Cumulative network input bytes for container".to_string(),
+		stat.net_input_bytes,
+		dimensions,
 	)?;
-	let net_output_gauge = get_gauge(
+	let net_output_counter = get_counter(
 		"container_network_output_bytes".to_string(),
-		"Network output bytes for container".to_string(),
-		output,
-		&stat.container,
-		labels,
+		"Cumulative network output bytes for container".to_string(),
+		stat.net_output_bytes,
+		dimensions,
 	)?;
-
-	let (block_read, block_write) = parse_blockio_str(stat.block_io.as_str())?;
-	let block_read_gauge = get_gauge(
+	let block_read_counter = get_counter(
 		"container_block_read_bytes".to_string(),
-		"Block read bytes for container".to_string(),
-		block_read,
-		&stat.container,
-		labels,
+		"Cumulative block read bytes for container".to_string(),
+		stat.block_read_bytes,
+		dimensions,
 	)?;
-	let block_write_gauge = get_gauge(
+	let block_write_counter = get_counter(
 		"container_block_write_bytes".to_string(),
-		"Block write bytes for container".to_string(),
-		block_write,
-		&stat.container,
-		labels,
+		"Cumulative block write bytes for container".to_string(),
+		stat.block_write_bytes,
+		dimensions,
 	)?;
 
 	Ok(vec![
-		cpu_gauge,
-		mem_usage_gauge,
-		mem_limit_gauge,
-		net_input_gauge,
-		net_output_gauge,
-		block_read_gauge,
-		block_write_gauge,
+		net_input_counter,
+		net_output_counter,
+		block_read_counter,
+		block_write_counter,
 	])
 }
 
-fn get_prometheus_format(
-	stats: Vec<DockerContainerStats>,
+fn global_gauge(name: &str, help: &str, value: f64) -> Result<GenericGauge<AtomicF64>> {
+	let gauge = Gauge::with_opts(Opts::new(name, help))?;
+	gauge.set(value);
+	Ok(gauge)
+}
+
+fn gather_registry(
+	snapshot: &stats_cache::StatsSnapshot,
 	labels: &HashMap<String, String>,
-) -> Result<String> {
+	label_from: &HashMap<String, String>,
+) -> Result<Registry> {
 	let registry = Registry::new();
-	for container_stats in &stats {
-		for gauge in gauges_for_container(container_stats, labels)? {
+	for container_stats in &snapshot.stats {
+		let dimensions = dimensions_for(container_stats, labels, label_from);
+		for gauge in gauges_for_container(container_stats, &dimensions)? {
 			registry.register(Box::new(gauge))?;
 		}
+		for counter in counters_for_container(container_stats, &dimensions)? {
+			registry.register(Box::new(counter))?;
+		}
 	}
 
-	let mut buffer = vec![];
-	let encoder = TextEncoder::new();
+	registry.register(Box::new(global_gauge(
+		"docker_stats_last_scrape_timestamp_seconds",
+		"Unix timestamp of the last successful docker stats collection",
+		snapshot.last_scrape_timestamp_seconds,
+	)?))?;
+	registry.register(Box::new(global_gauge(
+		"docker_stats_scrape_duration_seconds",
+		"How long the last docker stats collection took",
+		snapshot.scrape_duration_seconds,
+	)?))?;
+
+	Ok(registry)
+}
+
+/// Renders the registry in the requested format. `format` is the
+/// operator-configured default; an `Accept` header asking for OpenMetrics
+/// overrides it, matching how real scrapers negotiate content type.
+fn render_metrics(
+	snapshot: &stats_cache::StatsSnapshot,
+	labels: &HashMap<String, String>,
+	label_from: &HashMap<String, String>,
+	format: Format,
+	accept_header: Option<&str>,
+) -> Result<(String, &'static str)> {
+	let registry = gather_registry(snapshot, labels, label_from)?;
 	let metric_families = registry.gather();
-	encoder.encode(&metric_families, &mut buffer)?;
 
-	let str = String::from_utf8(buffer)?;
-	Ok(str)
+	let wants_openmetrics = format == Format::Openmetrics
+		|| accept_header.is_some_and(|accept| accept.contains("application/openmetrics-text"));
+
+	if wants_openmetrics {
+		Ok((openmetrics::encode(&metric_families)?, openmetrics::CONTENT_TYPE))
+	} else {
+		let encoder = TextEncoder::new();
+		let mut buffer = vec![];
+		encoder.encode(&metric_families, &mut buffer)?;
+		Ok((String::from_utf8(buffer)?, encoder.format_type()))
+	}
+}
+
+async fn docker_stats_metrics(
+	cache: StatsCache,
+	labels: HashMap<String, String>,
+	label_from: HashMap<String, String>,
+	format: Format,
+	headers: HeaderMap,
+) -> ApiResult<(HeaderMap, String)> {
+	let snapshot = cache.read().await.clone();
+	let accept_header = headers
+		.get(axum::http::header::ACCEPT)
+		.and_then(|value| value.to_str().ok());
+
+	let (body, content_type) = render_metrics(&snapshot, &labels, &label_from, format, accept_header)?;
+
+	let mut response_headers = HeaderMap::new();
+	response_headers.insert(axum::http::header::CONTENT_TYPE, content_type.parse()?);
+	Ok((response_headers, body))
 }
 
-async fn docker_stats_metrics(labels: HashMap<String, String>) -> ApiResult<String> {
-	let stats = docker::stats()?;
-	let prometheus_stuff = get_prometheus_format(stats, &labels)?;
-	Ok(prometheus_stuff)
+async fn health() -> &'static str {
+	"OK"
+}
+
+/// Builds the metrics + health router shared by the primary listener and
+/// the optional second one bound on `--metrics-address`.
+fn build_metrics_router(
+	metrics_path: &str,
+	cache: StatsCache,
+	labels: HashMap<String, String>,
+	label_from: HashMap<String, String>,
+	format: Format,
+) -> Router {
+	Router::new()
+		.route(
+			metrics_path,
+			get(move |headers: HeaderMap| docker_stats_metrics(cache, labels, label_from, format, headers)),
+		)
+		.route("/health", get(health))
 }
 
 fn main() -> Result<()> {
 	let args = Args::parse();
 	let labels = parse_labels(args.labels.clone())?;
+	let label_from = parse_labels(args.label_from.clone())?;
 
 	// Create custom Tokio runtime with limited threads
 	let runtime = tokio::runtime::Builder::new_multi_thread()
@@ -250,10 +369,14 @@ fn main() -> Result<()> {
 		.enable_all()
 		.build()?;
 
-	runtime.block_on(async_main(args, labels))
+	runtime.block_on(async_main(args, labels, label_from))
 }
 
-async fn async_main(args: Args, labels: HashMap<String, String>) -> Result<()> {
+async fn async_main(
+	args: Args,
+	labels: HashMap<String, String>,
+	label_from: HashMap<String, String>,
+) -> Result<()> {
 	let env_filter = match args.log_level.to_lowercase().as_str() {
 		"error" => format!("docker_stats_exporter=error,tower_http=error"),
 		"warn" => format!("docker_stats_exporter=warn,tower_http=warn"),
@@ -281,16 +404,38 @@ async fn async_main(args: Args, labels: HashMap<String, String>) -> Result<()> {
 	if !labels.is_empty() {
 		tracing::info!("Using labels: {:?}", labels);
 	}
+	if !label_from.is_empty() {
+		tracing::info!("Promoting container labels: {:?}", label_from);
+	}
 
-	let app = Router::new()
-		.route(
-			"/docker-stats/metrics",
-			get({
-				let labels = labels.clone();
-				move || docker_stats_metrics(labels)
-			}),
-		)
-		.layer(TraceLayer::new_for_http());
+	let cache = stats_cache::new_cache();
+	let source = args.source;
+	tokio::spawn(stats_cache::run(
+		cache.clone(),
+		Duration::from_secs(args.scrape_interval),
+		move || collect_metrics(source),
+	));
+
+	let metrics_router = build_metrics_router(
+		&args.metrics_path,
+		cache.clone(),
+		labels.clone(),
+		label_from.clone(),
+		args.format,
+	);
+
+	if let Some(metrics_address) = args.metrics_address.clone() {
+		let metrics_app = metrics_router.clone().layer(TraceLayer::new_for_http());
+		let metrics_listener = tokio::net::TcpListener::bind(&metrics_address).await?;
+		tracing::info!("Also serving metrics on {}", metrics_address);
+		tokio::spawn(async move {
+			if let Err(err) = axum::serve(metrics_listener, metrics_app).await {
+				tracing::error!("Metrics listener on {} failed: {:#}", metrics_address, err);
+			}
+		});
+	}
+
+	let app = metrics_router.layer(TraceLayer::new_for_http());
 	let listener = tokio::net::TcpListener::bind(&args.bind_address).await?;
 	axum::serve(listener, app).await?;
 	Ok(())
@@ -0,0 +1,110 @@
+//! Minimal OpenMetrics text exposition encoder. The `prometheus` crate only
+//! ships a classic Prometheus `TextEncoder`, so this renders the same
+//! gathered `MetricFamily`s in OpenMetrics form instead: one `# UNIT` line
+//! per family (for the families where a unit makes sense) and a trailing
+//! `# EOF` marker, both of which let downstream tooling auto-scale axes
+//! instead of guessing from the metric name suffix.
+//!
+//! OpenMetrics requires the family name in `# HELP`/`# TYPE`/`# UNIT` to be
+//! the bare name with no `_total` suffix, and requires any declared
+//! unit to actually be a suffix of that bare name — only the sample itself
+//! carries the `_total` suffix for counters. Getting this wrong makes a
+//! conformant parser (promtool, prometheus' own OpenMetrics parser) reject
+//! the whole exposition.
+
+use anyhow::Result;
+use prometheus::proto::{MetricFamily, MetricType};
+use std::fmt::Write as _;
+
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+pub fn encode(metric_families: &[MetricFamily]) -> Result<String> {
+	let mut out = String::new();
+
+	for family in metric_families {
+		let metric_type = family.get_field_type();
+		let is_counter = metric_type == MetricType::COUNTER;
+
+		// OpenMetrics counter families are named without `_total`; it's
+		// added back only on the sample name below.
+		let base_name = if is_counter {
+			family.get_name().strip_suffix("_total").unwrap_or_else(|| family.get_name())
+		} else {
+			family.get_name()
+		};
+		let sample_name = if is_counter { format!("{}_total", base_name) } else { base_name.to_string() };
+
+		let type_str = match metric_type {
+			MetricType::COUNTER => "counter",
+			MetricType::GAUGE => "gauge",
+			_ => "unknown",
+		};
+
+		writeln!(out, "# HELP {} {}", base_name, escape_help(family.get_help()))?;
+		writeln!(out, "# TYPE {} {}", base_name, type_str)?;
+		if let Some(unit) = unit_for(base_name) {
+			writeln!(out, "# UNIT {} {}", base_name, unit)?;
+		}
+
+		for metric in family.get_metric() {
+			let labels = metric
+				.get_label()
+				.iter()
+				.map(|label| format!("{}=\"{}\"", label.get_name(), escape_label_value(label.get_value())))
+				.collect::<Vec<_>>()
+				.join(",");
+
+			let value = match metric_type {
+				MetricType::COUNTER => metric.get_counter().get_value(),
+				MetricType::GAUGE => metric.get_gauge().get_value(),
+				_ => 0.0,
+			};
+
+			writeln!(out, "{}{{{}}} {}", sample_name, labels, format_value(value))?;
+		}
+	}
+
+	writeln!(out, "# EOF")?;
+	Ok(out)
+}
+
+/// Declares `bytes` for the memory/IO families and `ratio` for the 0-1 CPU
+/// ratio gauge. `container_cpu_usage` itself is a 0-100 percentage rather
+/// than a ratio, so it's left unitless; `container_cpu_usage_ratio` carries
+/// the declared `ratio` unit the request asked for.
+fn unit_for(base_name: &str) -> Option<&'static str> {
+	if base_name.ends_with("_ratio") {
+		Some("ratio")
+	} else if base_name.ends_with("_bytes") {
+		Some("bytes")
+	} else {
+		None
+	}
+}
+
+/// Escapes a label value: backslashes, quotes (the value is wrapped in
+/// `"..."`), and newlines, which can't appear literally in the single-line
+/// exposition format.
+fn escape_label_value(value: &str) -> String {
+	value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Escapes HELP text: backslashes and newlines only — HELP isn't
+/// quote-delimited, so quotes don't need escaping.
+fn escape_help(value: &str) -> String {
+	value.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+/// Renders a sample value, mapping non-finite floats to the textual forms
+/// OpenMetrics requires instead of Rust's `NaN`/`inf`/`-inf`.
+fn format_value(value: f64) -> String {
+	if value.is_nan() {
+		"NaN".to_string()
+	} else if value == f64::INFINITY {
+		"+Inf".to_string()
+	} else if value == f64::NEG_INFINITY {
+		"-Inf".to_string()
+	} else {
+		value.to_string()
+	}
+}
@@ -0,0 +1,56 @@
+//! Background stats collector. Scraping `docker stats` (or the Engine API)
+//! on every HTTP request makes Prometheus scrape latency depend on how many
+//! containers are running, and lets concurrent scrapes pile up subprocesses.
+//! Instead we refresh a shared cache on a timer and serve whatever's in it.
+
+use crate::docker::ContainerMetrics;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+
+/// Most recent snapshot of container stats, plus when/how long it took to
+/// collect, so scrape staleness is observable.
+#[derive(Debug, Clone, Default)]
+pub struct StatsSnapshot {
+	pub stats: Vec<ContainerMetrics>,
+	pub last_scrape_timestamp_seconds: f64,
+	pub scrape_duration_seconds: f64,
+}
+
+pub type StatsCache = Arc<RwLock<StatsSnapshot>>;
+
+pub fn new_cache() -> StatsCache {
+	Arc::new(RwLock::new(StatsSnapshot::default()))
+}
+
+/// Runs forever, refreshing `cache` every `interval` by calling `collect`.
+/// A failed collection is logged and leaves the previous snapshot in place
+/// rather than clearing it out.
+pub async fn run<F, Fut>(cache: StatsCache, interval: Duration, collect: F)
+where
+	F: Fn() -> Fut,
+	Fut: std::future::Future<Output = anyhow::Result<Vec<ContainerMetrics>>>,
+{
+	let mut ticker = tokio::time::interval(interval);
+	loop {
+		ticker.tick().await;
+
+		let start = Instant::now();
+		match collect().await {
+			Ok(stats) => {
+				let snapshot = StatsSnapshot {
+					stats,
+					last_scrape_timestamp_seconds: SystemTime::now()
+						.duration_since(UNIX_EPOCH)
+						.map(|d| d.as_secs_f64())
+						.unwrap_or(0.0),
+					scrape_duration_seconds: start.elapsed().as_secs_f64(),
+				};
+				*cache.write().await = snapshot;
+			},
+			Err(err) => {
+				tracing::error!("Failed to refresh docker stats cache: {:#}", err);
+			},
+		}
+	}
+}